@@ -1,6 +1,9 @@
 use std::sync::Arc;
 
-use external_storage_server::{def::{ExternalStorageApiClient}, native::ServerContext};
+use external_storage_server::{
+    def::{ExternalStorageApiClient},
+    native::{shared_runtime, ServerContext, ServerExecutor},
+};
 use external_storage_server::service::DefaultExternalStorageService;
 use external_storage_server::direct::new_direct_client;
 use external_storage_server::native::new_dylib_client;
@@ -82,10 +85,15 @@ pub async fn write_file(client: &ExternalStorageApiClient, store_id: &str) {
 }
 
 pub fn create_server_context() -> std::io::Result<ServerContext> {
-    let runtime = ::tokio::runtime::Runtime::new()?;
+    // Reuse the process-wide shared runtime instead of spinning up a fresh
+    // one per embedded context, so the host controls worker-thread count in
+    // one place.
     let service = build_service();
 
-    Ok(ServerContext::new(Arc::new(runtime), service))
+    Ok(ServerContext::with_executor(
+        ServerExecutor::Owned(shared_runtime()),
+        service,
+    ))
 }
 
 #[no_mangle]
@@ -94,7 +102,7 @@ pub extern "C" fn server_external_storage_create_context() -> *mut c_void {
 }
 
 fn main() {
-    let mut rt = ::tokio::runtime::Runtime::new().unwrap();
+    let rt = shared_runtime();
 
     let client = build_dylib_client();
 
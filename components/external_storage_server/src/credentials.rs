@@ -0,0 +1,255 @@
+use std::{
+    io,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use rusoto_credential::{AwsCredentials, CredentialsError, ProvideAwsCredentials};
+use rusoto_sts::{AssumeRoleWithWebIdentityRequest, Sts, StsClient};
+
+/// Default window before expiry in which a cached credential is considered
+/// stale and re-fetched on the next use, rather than waiting until it has
+/// actually expired and failing a signed request.
+pub const DEFAULT_REFRESH_WINDOW: Duration = Duration::from_secs(60);
+
+/// A resolved set of AWS-style credentials, with an optional session token
+/// and expiry for the temporary credentials STS/web-identity flows hand out.
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+    pub expires_at: Option<SystemTime>,
+}
+
+impl Credentials {
+    fn is_fresh(&self, refresh_window: Duration) -> bool {
+        match self.expires_at {
+            // Static credentials (no expiry) are always fresh.
+            None => true,
+            Some(expiry) => match expiry.checked_sub(refresh_window) {
+                Some(refresh_at) => SystemTime::now() < refresh_at,
+                None => false,
+            },
+        }
+    }
+}
+
+/// Fetches a brand-new credential with no caching of its own. Wrapped by
+/// `CachingCredentialProvider`, which adds the expiry check and refresh
+/// window every `CredentialProvider` consumer relies on.
+#[async_trait]
+pub trait CredentialSource: Send + Sync {
+    async fn fetch(&self) -> io::Result<Credentials>;
+}
+
+/// One fixed access/secret key pair handed out forever, for deployments that
+/// configure static keys directly (today's `create_s3_storage` behavior).
+pub struct StaticCredentialSource(Credentials);
+
+impl StaticCredentialSource {
+    pub fn new(access_key: impl Into<String>, secret_key: impl Into<String>) -> Self {
+        Self(Credentials {
+            access_key: access_key.into(),
+            secret_key: secret_key.into(),
+            session_token: None,
+            expires_at: None,
+        })
+    }
+}
+
+#[async_trait]
+impl CredentialSource for StaticCredentialSource {
+    async fn fetch(&self) -> io::Result<Credentials> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Reads `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY`/`AWS_SESSION_TOKEN` from
+/// the process environment on every fetch, the way rotating-credential
+/// injectors (e.g. IRSA's sidecar, ECS task role refreshers) keep them
+/// up to date in place.
+///
+/// This does not yet poll EC2/ECS instance-metadata directly; that would
+/// need an HTTP client wired into this crate and is left as a follow-up.
+pub struct EnvCredentialSource;
+
+#[async_trait]
+impl CredentialSource for EnvCredentialSource {
+    async fn fetch(&self) -> io::Result<Credentials> {
+        let access_key = std::env::var("AWS_ACCESS_KEY_ID")
+            .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "AWS_ACCESS_KEY_ID is not set"))?;
+        let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY").map_err(|_| {
+            io::Error::new(io::ErrorKind::NotFound, "AWS_SECRET_ACCESS_KEY is not set")
+        })?;
+        let session_token = std::env::var("AWS_SESSION_TOKEN").ok();
+
+        Ok(Credentials {
+            access_key,
+            secret_key,
+            session_token,
+            expires_at: None,
+        })
+    }
+}
+
+/// Exchanges a web-identity token file (the OIDC token EKS/IRSA projects
+/// into the pod) for temporary STS credentials via
+/// `AssumeRoleWithWebIdentity`, re-reading the token file on every fetch so a
+/// rotated token is picked up without restarting the process.
+pub struct WebIdentityCredentialSource {
+    token_file: PathBuf,
+    role_arn: String,
+    role_session_name: String,
+    client: StsClient,
+}
+
+impl WebIdentityCredentialSource {
+    pub fn new(
+        token_file: impl Into<PathBuf>,
+        role_arn: impl Into<String>,
+        role_session_name: impl Into<String>,
+        region: rusoto_core::Region,
+    ) -> Self {
+        Self {
+            token_file: token_file.into(),
+            role_arn: role_arn.into(),
+            role_session_name: role_session_name.into(),
+            client: StsClient::new(region),
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialSource for WebIdentityCredentialSource {
+    async fn fetch(&self) -> io::Result<Credentials> {
+        let token = std::fs::read_to_string(&self.token_file)?.trim().to_owned();
+
+        let req = AssumeRoleWithWebIdentityRequest {
+            role_arn: self.role_arn.clone(),
+            role_session_name: self.role_session_name.clone(),
+            web_identity_token: token,
+            ..Default::default()
+        };
+
+        let resp = self
+            .client
+            .assume_role_with_web_identity(req)
+            .await
+            .map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("AssumeRoleWithWebIdentity failed: {}", err),
+                )
+            })?;
+
+        let creds = resp.credentials.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "AssumeRoleWithWebIdentity response carried no credentials",
+            )
+        })?;
+
+        let expires_at = SystemTime::UNIX_EPOCH
+            .checked_add(Duration::from_secs(creds.expiration.max(0) as u64))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("invalid credential expiration {}", creds.expiration),
+                )
+            })?;
+
+        Ok(Credentials {
+            access_key: creds.access_key_id,
+            secret_key: creds.secret_access_key,
+            session_token: Some(creds.session_token),
+            expires_at: Some(expires_at),
+        })
+    }
+}
+
+/// Resolves the current credential, transparently refreshing it first if it
+/// is missing or within its refresh window of expiring. Every caller that
+/// signs a request goes through this, so a long-lived uploader survives
+/// credential rotation without ever seeing a stale key.
+#[async_trait]
+pub trait CredentialProvider: Send + Sync {
+    async fn credentials(&self) -> io::Result<Credentials>;
+}
+
+/// Caches whatever `CredentialSource` it wraps, calling through to `fetch`
+/// again only once the cached credential is within `refresh_window` of
+/// expiring (or there is none cached yet). This is the one place the
+/// expiry-check-then-refresh invariant lives; `StaticCredentialSource`,
+/// `EnvCredentialSource`, and `WebIdentityCredentialSource` all just
+/// implement `fetch` and get it for free.
+pub struct CachingCredentialProvider<S> {
+    source: S,
+    refresh_window: Duration,
+    cached: Mutex<Option<Credentials>>,
+}
+
+impl<S: CredentialSource> CachingCredentialProvider<S> {
+    pub fn new(source: S, refresh_window: Duration) -> Self {
+        Self {
+            source,
+            refresh_window,
+            cached: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: CredentialSource> CredentialProvider for CachingCredentialProvider<S> {
+    async fn credentials(&self) -> io::Result<Credentials> {
+        {
+            let cached = self.cached.lock().unwrap();
+            if let Some(creds) = cached.as_ref() {
+                if creds.is_fresh(self.refresh_window) {
+                    return Ok(creds.clone());
+                }
+            }
+        }
+
+        let fresh = self.source.fetch().await?;
+        *self.cached.lock().unwrap() = Some(fresh.clone());
+        Ok(fresh)
+    }
+}
+
+/// Bridges this crate's async `CredentialProvider` into rusoto's
+/// `ProvideAwsCredentials`, so an `S3Storage` built with it signs every
+/// request off of whatever is currently cached/refreshed here instead of
+/// rusoto's own static/chain providers.
+#[derive(Clone)]
+pub struct RusotoCredentialsAdapter<P>(std::sync::Arc<P>);
+
+impl<P> RusotoCredentialsAdapter<P> {
+    pub fn new(provider: std::sync::Arc<P>) -> Self {
+        Self(provider)
+    }
+}
+
+impl<P: CredentialProvider + 'static> ProvideAwsCredentials for RusotoCredentialsAdapter<P> {
+    type Future =
+        std::pin::Pin<Box<dyn std::future::Future<Output = Result<AwsCredentials, CredentialsError>> + Send>>;
+
+    fn credentials(&self) -> Self::Future {
+        let provider = self.0.clone();
+        Box::pin(async move {
+            let creds = provider
+                .credentials()
+                .await
+                .map_err(|err| CredentialsError::new(err.to_string()))?;
+
+            Ok(AwsCredentials::new(
+                creds.access_key,
+                creds.secret_key,
+                creds.session_token,
+                creds.expires_at.map(chrono::DateTime::<chrono::Utc>::from),
+            ))
+        })
+    }
+}
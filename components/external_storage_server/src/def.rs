@@ -1,9 +1,49 @@
 use async_trait::async_trait;
+use futures::stream::{self, BoxStream, FuturesUnordered, StreamExt};
 use kvproto::extstorepb::*;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::Instrument;
 
 pub type RpcStatusResult<T> = std::result::Result<T, ::grpcio::RpcStatus>;
 pub type RpcErrResult<T> = std::result::Result<T, ::grpcio::Error>;
 
+/// How long a cached idempotent response is replayed before falling through
+/// to re-execution. Bounds `IDEMPOTENCY_CACHE`'s growth the same way
+/// `UploaderReaperConfig::ttl` bounds idle uploaders, just swept
+/// opportunistically alongside each insert rather than by a background task:
+/// this cache is a process-wide `lazy_static`, not owned by any one
+/// service's tokio runtime, so there is nothing to spawn a reaper on.
+const IDEMPOTENCY_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+lazy_static! {
+    // Caches a finished `CallResponse` by the idempotency key carried on the
+    // request's `Header`, so a redelivered `complete_upload`/`abort_upload`
+    // (or any other call a client chooses to key) replays the original
+    // result instead of re-executing a side effect that already happened.
+    // Keyed generically at the envelope layer rather than per service method,
+    // since that is where the idempotency key itself lives. Entries carry
+    // their insertion time so they expire after `IDEMPOTENCY_CACHE_TTL`
+    // instead of living for the lifetime of the process.
+    static ref IDEMPOTENCY_CACHE: Mutex<HashMap<String, (CallResponse, Instant)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Size of the fixed byte window carried by each chunk of a `write_file_stream`
+/// or `read_file_stream` call.
+pub const STREAM_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Target part size `upload_stream` buffers a caller's byte stream into
+/// before dispatching an `upload_part`. Kept comfortably above S3's 5 MiB
+/// per-part minimum so only the trailing part, which that minimum does not
+/// apply to, can come in smaller.
+pub const MULTIPART_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Number of `upload_part` calls `upload_stream` keeps in flight at once.
+pub const MULTIPART_MAX_CONCURRENCY: usize = 4;
+
 #[macro_export]
 macro_rules! impl_external_storage {
     (
@@ -39,18 +79,99 @@ macro_rules! impl_external_storage {
         #[async_trait]
         pub trait $ServiceExtName {
             async fn call(&self, req: $ExtRequestType) -> RpcStatusResult<$ExtResponseType>;
+
+            /// Runs a batch of inner requests under one envelope and returns
+            /// their responses in the same order, by default concurrently
+            /// (`futures::future::join_all`). `req.get_sequence()` forces
+            /// one-at-a-time execution that stops at the first error, for
+            /// callers whose requests have ordering dependencies on each
+            /// other (e.g. `create_uploader` followed by `upload_part`).
+            async fn call_batch(&self, req: BatchRequest) -> RpcStatusResult<BatchResponse>
+            where
+                Self: Sync,
+            {
+                let sequence = req.get_sequence();
+                let items = req.get_requests().to_vec();
+
+                let to_call_request = |item: CallRequestRequest| {
+                    let mut call_req = CallRequest::new();
+                    call_req.set_request_id(uuid::Uuid::new_v4().as_bytes().to_vec());
+                    call_req.set_request(item);
+                    call_req
+                };
+
+                let responses: Vec<RpcStatusResult<CallResponseResponse>> = if sequence {
+                    let mut responses = Vec::with_capacity(items.len());
+                    for item in items {
+                        match self.call(to_call_request(item)).await {
+                            Ok(resp) => responses.push(Ok(resp.response.unwrap_or_default())),
+                            Err(err) => {
+                                responses.push(Err(err));
+                                break;
+                            }
+                        }
+                    }
+                    responses
+                } else {
+                    let calls = items
+                        .into_iter()
+                        .map(|item| self.call(to_call_request(item)));
+                    ::futures::future::join_all(calls)
+                        .await
+                        .into_iter()
+                        .map(|result| result.map(|resp| resp.response.unwrap_or_default()))
+                        .collect()
+                };
+
+                let mut resp = BatchResponse::new();
+                resp.set_responses(protobuf::RepeatedField::from_vec(
+                    responses.into_iter().collect::<RpcStatusResult<Vec<_>>>()?,
+                ));
+                Ok(resp)
+            }
         }
 
         #[async_trait]
         impl $ServiceExtName for Box<&(dyn $ServiceName + Send + Sync)> {
             async fn call(&self, req: $ExtRequestType) -> RpcStatusResult<$ExtResponseType> {
+                // The idempotency key, if any, is a cross-cutting concern that
+                // lives on the envelope rather than any one inner message, so
+                // it is handled once here rather than threaded into every
+                // service method.
+                let idempotency_key = req.get_header().get_idempotency_key().to_owned();
+                if !idempotency_key.is_empty() {
+                    let cached = IDEMPOTENCY_CACHE
+                        .lock()
+                        .unwrap()
+                        .get(&idempotency_key)
+                        .filter(|(_, inserted_at)| inserted_at.elapsed() < IDEMPOTENCY_CACHE_TTL)
+                        .map(|(resp, _)| resp.clone());
+                    if let Some(cached) = cached {
+                        return Ok(cached);
+                    }
+                }
+
+                let resp = self.dispatch(req).await?;
+
+                if !idempotency_key.is_empty() {
+                    let mut cache = IDEMPOTENCY_CACHE.lock().unwrap();
+                    cache.retain(|_, (_, inserted_at)| inserted_at.elapsed() < IDEMPOTENCY_CACHE_TTL);
+                    cache.insert(idempotency_key, (resp.clone(), Instant::now()));
+                }
+
+                Ok(resp)
+            }
+        }
+
+        impl Box<&(dyn $ServiceName + Send + Sync)> {
+            async fn dispatch(&self, req: $ExtRequestType) -> RpcStatusResult<$ExtResponseType> {
                 if !req.has_request() {
                     return Err(::grpcio::RpcStatus::new(
                         ::grpcio::RpcStatusCode::INVALID_ARGUMENT,
                         Some("request message is empty".to_owned())
                     ));
                 }
-            
+
                 let message = req.request.unwrap().message;
                 if message.is_none() {
                     return Err(::grpcio::RpcStatus::new(
@@ -58,22 +179,37 @@ macro_rules! impl_external_storage {
                         Some("request message is empty".to_owned())
                     ));
                 }
-            
-                let mut inner_resp = CallResponseResponse::new();
-                inner_resp.message = match message.unwrap() {
+
+                match message.unwrap() {
+                    CallRequest_Request_oneof_message::BatchRequest(batch) => {
+                        let inner_resp = self.call_batch(batch).await?;
+                        let mut resp = CallResponse::new();
+                        resp.set_request_id(req.request_id);
+                        let mut wrapped = CallResponseResponse::new();
+                        wrapped.message = Some(CallResponse_Response_oneof_message::BatchResponse(inner_resp));
+                        resp.set_response(wrapped);
+                        Ok(resp)
+                    }
                     $(
-                        CallRequest_Request_oneof_message::$ServiceRequestType(inner_req) => { 
-                            Some(CallResponse_Response_oneof_message::$ServiceResponseType(
+                        CallRequest_Request_oneof_message::$ServiceRequestType(inner_req) => {
+                            let mut inner_resp = CallResponseResponse::new();
+                            inner_resp.message = Some(CallResponse_Response_oneof_message::$ServiceResponseType(
                                 self.$service_fn_name(inner_req).await?
-                            ))
+                            ));
+
+                            let mut resp = CallResponse::new();
+                            resp.set_request_id(req.request_id);
+                            resp.set_response(inner_resp);
+                            Ok(resp)
                         }
                     )*
-                };
-            
-                let mut resp = CallResponse::new();
-                resp.set_request_id(req.request_id);
-                resp.set_response(inner_resp);
-                Ok(resp)
+                    _ => {
+                        Err(::grpcio::RpcStatus::new(
+                            ::grpcio::RpcStatusCode::UNIMPLEMENTED,
+                            Some("unsupported call message type".to_owned())
+                        ))
+                    }
+                }
             }
         }
 
@@ -104,6 +240,36 @@ macro_rules! impl_external_storage {
         $(#[$raw_client_outer])*
         pub trait $RawClientName {
             async fn $raw_client_fn_name(&self, req: &$RawClientRequestType) -> RpcErrResult<$RawClientResponseType>;
+
+            /// Deadline-bounded variant of `call`. The default ignores `deadline`
+            /// and simply forwards to `call`; transports that can actually race
+            /// the in-flight request against a timer (currently the dylib
+            /// client, where a stuck request would otherwise leak forever)
+            /// override it.
+            async fn call_with_deadline(
+                &self,
+                req: &$RawClientRequestType,
+                deadline: Option<::std::time::Duration>,
+            ) -> RpcErrResult<$RawClientResponseType> {
+                let _ = deadline;
+                self.$raw_client_fn_name(req).await
+            }
+
+            /// Client-streaming variant of `call`: frames are sent one at a time
+            /// instead of buffering the whole payload into a single request, so a
+            /// multi-gigabyte `write_file` upload never has to live in memory in
+            /// full on either side of the call.
+            async fn call_stream(
+                &self,
+                reqs: BoxStream<'static, $RawClientRequestType>,
+            ) -> RpcErrResult<$RawClientResponseType>;
+
+            /// Server-streaming counterpart of `call`, used by `read_file_stream`
+            /// so restore can pull large objects without buffering them either.
+            async fn call_response_stream(
+                &self,
+                req: &$RawClientRequestType,
+            ) -> RpcErrResult<BoxStream<'static, RpcErrResult<$RawClientResponseType>>>;
         }
 
         $(#[$client_outer])*
@@ -120,42 +286,59 @@ macro_rules! impl_external_storage {
 
             $(
                 pub async fn $service_fn_name(&self, req: &$ServiceRequestType) -> RpcErrResult<$ServiceResponseType> {
-                    let mut inner_req = CallRequestRequest::new();
-                    inner_req.message = Some(
-                        CallRequest_Request_oneof_message::$ServiceRequestType(req.clone())
+                    let request_id = uuid::Uuid::new_v4();
+                    // The dylib transport loses the Rust call stack at the `extern
+                    // "C"` boundary, so the span context is carried across it
+                    // manually via the header's `trace_id` rather than relying on
+                    // any in-process span propagation.
+                    let span = ::tracing::info_span!(
+                        stringify!($service_fn_name),
+                        request_id = %request_id,
                     );
-            
-                    let mut call_req = CallRequest::new();
-                    call_req.set_request_id(uuid::Uuid::new_v4().as_bytes().to_vec());
-                    call_req.set_request(inner_req);
-            
-                    let call_resp = self.$ref_raw_client.$raw_client_fn_name(&call_req).await?;
-                    if !call_resp.has_response() {
-                        return Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
-                            ::grpcio::RpcStatusCode::INTERNAL,
-                            Some("faild to get response".to_owned())
-                        )));
-                    }
-            
-                    let message = call_resp.response.unwrap().message;
-                    if message.is_none() {
-                        return Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
-                            ::grpcio::RpcStatusCode::INTERNAL,
-                            Some("response message is empty".to_owned())
-                        )));
-                    }
-            
-                    match message.unwrap() {
-                        CallResponse_Response_oneof_message::$ServiceResponseType(inner_resp) => { 
-                            Ok(inner_resp)
-                        }
-                        _ => {
+                    async move {
+                        let mut inner_req = CallRequestRequest::new();
+                        inner_req.message = Some(
+                            CallRequest_Request_oneof_message::$ServiceRequestType(req.clone())
+                        );
+
+                        let mut header = Header::new();
+                        header.set_trace_id(request_id.to_string());
+
+                        let mut call_req = CallRequest::new();
+                        call_req.set_request_id(request_id.as_bytes().to_vec());
+                        call_req.set_request(inner_req);
+                        call_req.set_header(header);
+
+                        let call_resp = self.$ref_raw_client.$raw_client_fn_name(&call_req).await?;
+                        if !call_resp.has_response() {
                             return Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
                                 ::grpcio::RpcStatusCode::INTERNAL,
                                 Some("faild to get response".to_owned())
-                            )))
+                            )));
+                        }
+
+                        let message = call_resp.response.unwrap().message;
+                        if message.is_none() {
+                            return Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                                ::grpcio::RpcStatusCode::INTERNAL,
+                                Some("response message is empty".to_owned())
+                            )));
+                        }
+
+                        match message.unwrap() {
+                            CallResponse_Response_oneof_message::$ServiceResponseType(inner_resp) => {
+                                Ok(inner_resp)
+                            }
+                            _ => {
+                                Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                                    ::grpcio::RpcStatusCode::INTERNAL,
+                                    Some("faild to get response".to_owned())
+                                )))
+                            }
                         }
                     }
+                    .instrument(span)
+                    .await
                 }
             )*
         }
@@ -167,7 +350,12 @@ impl_external_storage!(
     pub trait ExternalStorageService {
         async fn list_store(&self, req: ListStoreRequest) -> RpcStatusResult<ListStoreResponse>;
         async fn get_store(&self, req: GetStoreRequest) -> RpcStatusResult<GetStoreResponse>;
+        async fn create_store(&self, req: CreateStoreRequest) -> RpcStatusResult<CreateStoreResponse>;
+        async fn delete_store(&self, req: DeleteStoreRequest) -> RpcStatusResult<DeleteStoreResponse>;
         async fn write_file(&self, req: WriteFileRequest) -> RpcStatusResult<WriteFileResponse>;
+        async fn read_file(&self, req: ReadFileRequest) -> RpcStatusResult<ReadFileResponse>;
+        async fn list_files(&self, req: ListFilesRequest) -> RpcStatusResult<ListFilesResponse>;
+        async fn head_object(&self, req: HeadObjectRequest) -> RpcStatusResult<HeadObjectResponse>;
         async fn create_uploader(&self, req: CreateUploaderRequest) -> RpcStatusResult<CreateUploaderResponse>;
         async fn upload_part(&self, req: UploadPartRequest) -> RpcStatusResult<UploadPartResponse>;
         async fn complete_upload(&self, req: CompleteUploadRequest) -> RpcStatusResult<CompleteUploadResponse>;
@@ -187,4 +375,405 @@ impl_external_storage!(
     pub struct ExternalStorageApiClient {
         client: Box<dyn ExternalStorageRawClient + Send + Sync>
     }
-);
\ No newline at end of file
+);
+
+fn wrap_call_request(message: CallRequest_Request_oneof_message) -> CallRequest {
+    wrap_call_request_with_id(message, uuid::Uuid::new_v4().as_bytes().to_vec())
+}
+
+/// Like `wrap_call_request`, but under a caller-supplied `request_id` instead
+/// of a freshly generated one. Used to build the frames of a single
+/// `call_stream` call, which must all share one id so the receiving side can
+/// demultiplex them onto the same in-flight stream (see `call_stream`'s doc
+/// comment on `ExternalStorageRawClient`).
+fn wrap_call_request_with_id(
+    message: CallRequest_Request_oneof_message,
+    request_id: Vec<u8>,
+) -> CallRequest {
+    let mut inner_req = CallRequestRequest::new();
+    inner_req.message = Some(message);
+
+    let mut call_req = CallRequest::new();
+    call_req.set_request_id(request_id);
+    call_req.set_request(inner_req);
+    call_req.set_header(Header::new());
+    call_req
+}
+
+/// Rebuffers a stream of variable-sized byte chunks into a stream of
+/// exactly-`chunk_size` chunks, with the last chunk possibly shorter. Used by
+/// `upload_stream` to turn whatever granularity a caller hands in into
+/// S3-minimum-respecting multipart parts.
+fn rechunk(
+    data: BoxStream<'static, Vec<u8>>,
+    chunk_size: usize,
+) -> impl futures::Stream<Item = Vec<u8>> {
+    stream::unfold(
+        (data, Vec::new(), false),
+        move |(mut data, mut buf, done)| async move {
+            if done {
+                return None;
+            }
+            loop {
+                if buf.len() >= chunk_size {
+                    let rest = buf.split_off(chunk_size);
+                    return Some((buf, (data, rest, false)));
+                }
+                match data.next().await {
+                    Some(piece) => buf.extend_from_slice(&piece),
+                    None if buf.is_empty() => return None,
+                    None => return Some((buf, (data, Vec::new(), true))),
+                }
+            }
+        },
+    )
+}
+
+fn batch_item(message: CallRequest_Request_oneof_message) -> CallRequestRequest {
+    let mut item = CallRequestRequest::new();
+    item.message = Some(message);
+    item
+}
+
+/// Chainable builder for the per-call metadata carried on `CallRequest`'s
+/// `Header`: a deadline, a trace/correlation id, and an idempotency key.
+/// Kept separate from the generated per-service-method helpers (`write_file`,
+/// `upload_part`, ...) so none of their signatures need to change just to let
+/// a caller opt into these; instead a caller builds the inner oneof message
+/// by hand and routes it through here.
+pub struct CallBuilder<'a> {
+    client: &'a ExternalStorageApiClient,
+    header: Header,
+}
+
+impl<'a> CallBuilder<'a> {
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.header.set_deadline_ms(deadline.as_millis() as u64);
+        self
+    }
+
+    pub fn trace_id(mut self, trace_id: impl Into<String>) -> Self {
+        self.header.set_trace_id(trace_id.into());
+        self
+    }
+
+    /// Requests at-most-once execution: a redelivery of a call carrying the
+    /// same key replays the cached response instead of re-running it.
+    pub fn idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.header.set_idempotency_key(key.into());
+        self
+    }
+
+    pub async fn call(
+        self,
+        message: CallRequest_Request_oneof_message,
+    ) -> RpcErrResult<CallResponse_Response_oneof_message> {
+        let deadline = if self.header.get_deadline_ms() > 0 {
+            Some(Duration::from_millis(self.header.get_deadline_ms()))
+        } else {
+            None
+        };
+
+        let mut call_req = wrap_call_request(message);
+        call_req.set_header(self.header);
+
+        let call_resp = self
+            .client
+            .client
+            .call_with_deadline(&call_req, deadline)
+            .await?;
+        if !call_resp.has_response() {
+            return Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                ::grpcio::RpcStatusCode::INTERNAL,
+                Some("faild to get response".to_owned()),
+            )));
+        }
+
+        call_resp.response.unwrap().message.ok_or_else(|| {
+            ::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                ::grpcio::RpcStatusCode::INTERNAL,
+                Some("response message is empty".to_owned()),
+            ))
+        })
+    }
+}
+
+impl ExternalStorageApiClient {
+    /// Starts a call that can carry a deadline, trace id, and/or idempotency
+    /// key on its `Header`. See `CallBuilder`.
+    pub fn with_header(&self) -> CallBuilder<'_> {
+        CallBuilder {
+            client: self,
+            header: Header::new(),
+        }
+    }
+
+    /// Dispatches a vector of inner requests (mixing `list_store`,
+    /// `write_file`, `upload_part`, etc.) under a single `CallRequest`
+    /// envelope and returns their responses in the same order. By default the
+    /// server runs them concurrently; pass `sequence: true` to force
+    /// one-at-a-time execution that stops at the first error, for callers
+    /// whose requests depend on each other completing in order.
+    pub async fn batch(
+        &self,
+        items: Vec<CallRequest_Request_oneof_message>,
+        sequence: bool,
+    ) -> RpcErrResult<Vec<CallResponseResponse>> {
+        let mut batch_req = BatchRequest::new();
+        batch_req.set_requests(protobuf::RepeatedField::from_vec(
+            items.into_iter().map(batch_item).collect(),
+        ));
+        batch_req.set_sequence(sequence);
+
+        let call_req = wrap_call_request(CallRequest_Request_oneof_message::BatchRequest(batch_req));
+        let call_resp = self.client.call(&call_req).await?;
+        if !call_resp.has_response() {
+            return Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                ::grpcio::RpcStatusCode::INTERNAL,
+                Some("faild to get response".to_owned()),
+            )));
+        }
+
+        match call_resp.response.unwrap().message {
+            Some(CallResponse_Response_oneof_message::BatchResponse(mut resp)) => {
+                Ok(resp.take_responses().into_vec())
+            }
+            _ => Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                ::grpcio::RpcStatusCode::INTERNAL,
+                Some("faild to get response".to_owned()),
+            ))),
+        }
+    }
+
+    /// Streams `data` to the store in fixed-size windows instead of buffering
+    /// the whole file into a single `WriteFileRequest`. The first frame on the
+    /// wire carries only the destination metadata, every following frame
+    /// carries up to `STREAM_CHUNK_SIZE` bytes, and an empty final frame
+    /// commits the upload.
+    pub async fn write_file_stream(
+        &self,
+        store_id: &str,
+        filepath: &str,
+        total_length: u64,
+        data: BoxStream<'static, Vec<u8>>,
+    ) -> RpcErrResult<WriteFileResponse> {
+        let mut meta = WriteFileRequest::new();
+        meta.set_store_id(store_id.to_owned());
+        meta.set_filepath(filepath.to_owned());
+        meta.set_total_length(total_length);
+
+        // All frames of this stream must share one `request_id` so the
+        // receiving side can demultiplex them onto the same in-flight
+        // stream instead of treating each as an unrelated call.
+        let request_id = uuid::Uuid::new_v4().as_bytes().to_vec();
+        let meta_frame = futures::stream::once({
+            let request_id = request_id.clone();
+            async move {
+                wrap_call_request_with_id(
+                    CallRequest_Request_oneof_message::WriteFileRequest(meta),
+                    request_id,
+                )
+            }
+        });
+        let data_frames = rechunk(data, STREAM_CHUNK_SIZE).map({
+            let request_id = request_id.clone();
+            move |chunk| {
+                let mut req = WriteFileRequest::new();
+                req.set_data(chunk);
+                wrap_call_request_with_id(
+                    CallRequest_Request_oneof_message::WriteFileRequest(req),
+                    request_id.clone(),
+                )
+            }
+        });
+        let commit_frame = futures::stream::once(async move {
+            wrap_call_request_with_id(
+                CallRequest_Request_oneof_message::WriteFileRequest(WriteFileRequest::new()),
+                request_id,
+            )
+        });
+
+        let call_resp = self
+            .client
+            .call_stream(Box::pin(meta_frame.chain(data_frames).chain(commit_frame)))
+            .await?;
+        if !call_resp.has_response() {
+            return Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                ::grpcio::RpcStatusCode::INTERNAL,
+                Some("faild to get response".to_owned()),
+            )));
+        }
+
+        match call_resp.response.unwrap().message {
+            Some(CallResponse_Response_oneof_message::WriteFileResponse(resp)) => Ok(resp),
+            _ => Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                ::grpcio::RpcStatusCode::INTERNAL,
+                Some("faild to get response".to_owned()),
+            ))),
+        }
+    }
+
+    /// Streaming counterpart of `upload_part`, for pushing a single multipart
+    /// upload part without buffering it whole: the first frame carries the
+    /// destination uploader metadata, every following frame up to
+    /// `STREAM_CHUNK_SIZE` bytes of part data, mirroring `write_file_stream`.
+    pub async fn upload_part_stream(
+        &self,
+        store_id: &str,
+        uploader_id: &str,
+        part_number: u64,
+        data: BoxStream<'static, Vec<u8>>,
+    ) -> RpcErrResult<UploadPartResponse> {
+        let mut meta = UploadPartRequest::new();
+        meta.set_store_id(store_id.to_owned());
+        meta.set_uploader_id(uploader_id.to_owned());
+        meta.set_part_number(part_number);
+
+        // All frames of this stream must share one `request_id`; see the
+        // matching comment in `write_file_stream`.
+        let request_id = uuid::Uuid::new_v4().as_bytes().to_vec();
+        let meta_frame = futures::stream::once({
+            let request_id = request_id.clone();
+            async move {
+                wrap_call_request_with_id(
+                    CallRequest_Request_oneof_message::UploadPartRequest(meta),
+                    request_id,
+                )
+            }
+        });
+        let data_frames = rechunk(data, STREAM_CHUNK_SIZE).map(move |chunk| {
+            let mut req = UploadPartRequest::new();
+            req.set_data(chunk);
+            wrap_call_request_with_id(
+                CallRequest_Request_oneof_message::UploadPartRequest(req),
+                request_id.clone(),
+            )
+        });
+
+        let call_resp = self
+            .client
+            .call_stream(Box::pin(meta_frame.chain(data_frames)))
+            .await?;
+        if !call_resp.has_response() {
+            return Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                ::grpcio::RpcStatusCode::INTERNAL,
+                Some("faild to get response".to_owned()),
+            )));
+        }
+
+        match call_resp.response.unwrap().message {
+            Some(CallResponse_Response_oneof_message::UploadPartResponse(resp)) => Ok(resp),
+            _ => Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                ::grpcio::RpcStatusCode::INTERNAL,
+                Some("faild to get response".to_owned()),
+            ))),
+        }
+    }
+
+    /// Streams `data` into the store through the multipart uploader path
+    /// instead of one frame per call: buffers the stream into fixed
+    /// `MULTIPART_CHUNK_SIZE` parts (only the trailing part may be smaller),
+    /// and keeps up to `MULTIPART_MAX_CONCURRENCY` `upload_part` calls in
+    /// flight concurrently rather than round-tripping one part at a time.
+    /// Completes the upload once every part has landed; on any part failure,
+    /// aborts it instead so the store is not left with a dangling multipart
+    /// upload.
+    pub async fn upload_stream(
+        &self,
+        store_id: &str,
+        filepath: &str,
+        data: BoxStream<'static, Vec<u8>>,
+    ) -> RpcErrResult<CompleteUploadResponse> {
+        let mut create_req = CreateUploaderRequest::new();
+        create_req.set_store_id(store_id.to_owned());
+        create_req.set_filepath(filepath.to_owned());
+        let create_resp = self.create_uploader(&create_req).await?;
+        let uploader_id = create_resp.get_uploader().get_id().to_owned();
+
+        match self.upload_stream_parts(store_id, &uploader_id, data).await {
+            Ok(()) => {
+                let mut complete_req = CompleteUploadRequest::new();
+                complete_req.set_store_id(store_id.to_owned());
+                complete_req.set_uploader_id(uploader_id);
+                self.complete_upload(&complete_req).await
+            }
+            Err(err) => {
+                let mut abort_req = AbortUploadRequest::new();
+                abort_req.set_store_id(store_id.to_owned());
+                abort_req.set_uploader_id(uploader_id);
+                // Best-effort: the original failure is what the caller needs
+                // to see, even if the cleanup abort itself also fails.
+                let _ = self.abort_upload(&abort_req).await;
+                Err(err)
+            }
+        }
+    }
+
+    async fn upload_stream_parts(
+        &self,
+        store_id: &str,
+        uploader_id: &str,
+        data: BoxStream<'static, Vec<u8>>,
+    ) -> RpcErrResult<()> {
+        let mut chunks = Box::pin(rechunk(data, MULTIPART_CHUNK_SIZE));
+        let mut exhausted = false;
+        let mut part_number: u64 = 0;
+        let mut in_flight = FuturesUnordered::new();
+
+        loop {
+            while !exhausted && in_flight.len() < MULTIPART_MAX_CONCURRENCY {
+                match chunks.next().await {
+                    Some(chunk) => {
+                        part_number += 1;
+                        let mut req = UploadPartRequest::new();
+                        req.set_store_id(store_id.to_owned());
+                        req.set_uploader_id(uploader_id.to_owned());
+                        req.set_part_number(part_number);
+                        req.set_data(chunk);
+                        in_flight.push(async move { self.upload_part(&req).await });
+                    }
+                    None => exhausted = true,
+                }
+            }
+
+            if in_flight.is_empty() {
+                return Ok(());
+            }
+
+            in_flight.next().await.unwrap()?;
+        }
+    }
+
+    /// Symmetric counterpart of `write_file_stream`: pulls a large object back
+    /// as a stream of byte windows rather than a single buffered response, so
+    /// restore never has to hold the whole object in memory.
+    pub async fn read_file_stream(
+        &self,
+        req: &ReadFileRequest,
+    ) -> RpcErrResult<BoxStream<'static, RpcErrResult<Vec<u8>>>> {
+        let call_req = wrap_call_request(CallRequest_Request_oneof_message::ReadFileRequest(
+            req.clone(),
+        ));
+        let call_resps = self.client.call_response_stream(&call_req).await?;
+
+        Ok(Box::pin(call_resps.map(|call_resp| {
+            let call_resp = call_resp?;
+            if !call_resp.has_response() {
+                return Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                    ::grpcio::RpcStatusCode::INTERNAL,
+                    Some("faild to get response".to_owned()),
+                )));
+            }
+
+            match call_resp.response.unwrap().message {
+                Some(CallResponse_Response_oneof_message::ReadFileResponse(resp)) => {
+                    Ok(resp.get_data().to_owned())
+                }
+                _ => Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                    ::grpcio::RpcStatusCode::INTERNAL,
+                    Some("faild to get response".to_owned()),
+                ))),
+            }
+        })))
+    }
+}
\ No newline at end of file
@@ -1,9 +1,49 @@
 use async_trait::async_trait;
-use kvproto::extstorepb::{CallRequest, CallResponse};
+use futures::stream::{BoxStream, StreamExt};
+use kvproto::extstorepb::{
+    CallRequest, CallRequest_Request_oneof_message, CallResponse,
+    CallResponse_Response_oneof_message,
+};
 
 use crate::def::*;
 use crate::util::{pb_marshal, pb_unmarshal};
 
+pub(crate) fn invalid_argument(msg: &str) -> ::grpcio::Error {
+    ::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+        ::grpcio::RpcStatusCode::INVALID_ARGUMENT,
+        Some(msg.to_owned()),
+    ))
+}
+
+/// Appends `chunk`'s frame onto `acc`'s, for the message types `call_stream`
+/// supports chunking: both carry their payload in a `data` field and nothing
+/// else changes frame-to-frame. Shared with `grpc.rs`, which reassembles the
+/// same frames off a real gRPC client-streaming call instead of an in-process
+/// one.
+pub(crate) fn merge_stream_frame(
+    acc: CallRequest_Request_oneof_message,
+    chunk: CallRequest_Request_oneof_message,
+) -> RpcErrResult<CallRequest_Request_oneof_message> {
+    use CallRequest_Request_oneof_message::*;
+    match (acc, chunk) {
+        (WriteFileRequest(mut acc), WriteFileRequest(chunk)) => {
+            let mut data = acc.take_data();
+            data.extend_from_slice(chunk.get_data());
+            acc.set_data(data);
+            Ok(WriteFileRequest(acc))
+        }
+        (UploadPartRequest(mut acc), UploadPartRequest(chunk)) => {
+            let mut data = acc.take_data();
+            data.extend_from_slice(chunk.get_data());
+            acc.set_data(data);
+            Ok(UploadPartRequest(acc))
+        }
+        _ => Err(invalid_argument(
+            "call_stream frames must all share the same message type",
+        )),
+    }
+}
+
 #[derive(Clone)]
 struct DirectRawClient<T: ExternalStorageService + Send + Sync + 'static> {
     service: T,
@@ -27,6 +67,87 @@ impl<T: ExternalStorageService + Send + Sync + 'static> ExternalStorageRawClient
             Err(err) => Err(::grpcio::Error::RpcFailure(err)),
         }
     }
+
+    async fn call_stream(&self, mut reqs: BoxStream<'static, CallRequest>) -> RpcErrResult<CallResponse> {
+        // There is no real transport between `DirectRawClient` and the service it
+        // wraps, so there is nothing to gain from forwarding chunks one at a
+        // time: reassemble them into the single request the service already
+        // knows how to handle (`write_file_stream` merges `WriteFileRequest`
+        // frames, `upload_part_stream` merges `UploadPartRequest` frames).
+        let mut merged: Option<CallRequest_Request_oneof_message> = None;
+        while let Some(call_req) = reqs.next().await {
+            let message = call_req
+                .request
+                .and_then(|r| r.message)
+                .ok_or_else(|| invalid_argument("request message is empty"))?;
+            merged = Some(match merged {
+                None => message,
+                Some(acc) => merge_stream_frame(acc, message)?,
+            });
+        }
+
+        let message = merged.ok_or_else(|| invalid_argument("call_stream received no frames"))?;
+
+        let mut call_req = CallRequest::new();
+        call_req.set_request_id(uuid::Uuid::new_v4().as_bytes().to_vec());
+        let mut inner = kvproto::extstorepb::CallRequestRequest::new();
+        inner.message = Some(message);
+        call_req.set_request(inner);
+
+        self.call(&call_req).await
+    }
+
+    async fn call_response_stream(
+        &self,
+        req: &CallRequest,
+    ) -> RpcErrResult<BoxStream<'static, RpcErrResult<CallResponse>>> {
+        // Same reasoning as `call_stream`: chunk the single buffered response up
+        // after the fact rather than threading a real stream through the
+        // in-process service.
+        let resp = self.call(req).await?;
+        let chunks: Vec<RpcErrResult<CallResponse>> =
+            chunk_call_response(resp).into_iter().map(Ok).collect();
+
+        Ok(Box::pin(futures::stream::iter(chunks)))
+    }
+}
+
+/// Splits a single buffered `CallResponse`'s `ReadFileResponse` payload (if
+/// any) into `STREAM_CHUNK_SIZE` windows, each wrapped in its own
+/// `CallResponse` carrying the original `request_id`. Non-`ReadFileResponse`
+/// responses have no payload to split and come back as a single chunk.
+/// Shared with `grpc.rs`, which streams these chunks out over a real gRPC
+/// server-streaming call instead of a `futures::stream::iter`.
+pub(crate) fn chunk_call_response(resp: CallResponse) -> Vec<CallResponse> {
+    let data = match &resp.response {
+        Some(r) => match &r.message {
+            Some(CallResponse_Response_oneof_message::ReadFileResponse(read_resp)) => {
+                Some(read_resp.get_data().to_owned())
+            }
+            _ => None,
+        },
+        None => None,
+    };
+    let data = match data {
+        Some(data) => data,
+        None => return vec![resp],
+    };
+
+    let request_id = resp.get_request_id().to_owned();
+    data.chunks(STREAM_CHUNK_SIZE)
+        .map(|window| {
+            let mut chunk_resp = CallResponse::new();
+            chunk_resp.set_request_id(request_id.clone());
+            let mut read_resp = kvproto::extstorepb::ReadFileResponse::new();
+            read_resp.set_data(window.to_vec());
+            let mut inner = kvproto::extstorepb::CallResponseResponse::new();
+            inner.message = Some(CallResponse_Response_oneof_message::ReadFileResponse(
+                read_resp,
+            ));
+            chunk_resp.set_response(inner);
+            chunk_resp
+        })
+        .collect()
 }
 
 pub fn new_direct_client<T: ExternalStorageService + Send + Sync + 'static>(service: T) -> ExternalStorageApiClient {
@@ -1,8 +1,14 @@
 use crate::def::*;
+use crate::direct::{chunk_call_response, invalid_argument, merge_stream_frame};
 use async_trait::async_trait;
-use std::sync::Arc;
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
 
-use kvproto::extstorepb::{CallRequest, CallResponse};
+use kvproto::extstorepb::{CallRequest, CallRequestRequest, CallResponse};
 use kvproto::extstorepb_grpc::{ExternalStorage, ExternalStorageClient as PbRpcClient};
 
 #[derive(Clone)]
@@ -39,27 +45,235 @@ impl<T: ExternalStorageService + Send + Sync + Clone + 'static> ExternalStorage
             }
         });
     }
+
+    fn call_stream(
+        &mut self,
+        _: grpcio::RpcContext,
+        stream: grpcio::RequestStream<CallRequest>,
+        sink: grpcio::ClientStreamingSink<CallResponse>,
+    ) {
+        let service = self.service.clone();
+        self.runtime.spawn(async move {
+            match merge_call_stream(stream).await {
+                Ok(req) => match service.call(req).await {
+                    Ok(res) => sink.success(res),
+                    Err(err) => sink.fail(err),
+                },
+                Err(err) => sink.fail(::grpcio::RpcStatus::new(
+                    ::grpcio::RpcStatusCode::INVALID_ARGUMENT,
+                    Some(err.to_string()),
+                )),
+            }
+        });
+    }
+
+    fn call_response_stream(
+        &mut self,
+        _: grpcio::RpcContext,
+        req: CallRequest,
+        mut sink: grpcio::ServerStreamingSink<CallResponse>,
+    ) {
+        let service = self.service.clone();
+        self.runtime.spawn(async move {
+            let resp = match service.call(req).await {
+                Ok(resp) => resp,
+                Err(err) => return sink.fail(err),
+            };
+
+            for chunk in chunk_call_response(resp) {
+                if let Err(err) = sink.send((chunk, ::grpcio::WriteFlags::default())).await {
+                    ::tracing::warn!(error = %err, "failed to send call_response_stream chunk");
+                    return;
+                }
+            }
+            let _ = sink.close().await;
+        });
+    }
 }
 
-struct RpcRawClient {
-    client: PbRpcClient,
+/// Reassembles the frames of a real gRPC client-streaming `call_stream` call
+/// into the single `CallRequest` `ExternalStorageServiceExt::call` knows how
+/// to handle, mirroring `DirectRawClient::call_stream`'s in-process
+/// reassembly (see `merge_stream_frame`).
+async fn merge_call_stream(
+    mut stream: grpcio::RequestStream<CallRequest>,
+) -> RpcErrResult<CallRequest> {
+    let mut merged = None;
+    while let Some(call_req) = stream.try_next().await? {
+        let message = call_req
+            .request
+            .and_then(|r| r.message)
+            .ok_or_else(|| invalid_argument("request message is empty"))?;
+        merged = Some(match merged {
+            None => message,
+            Some(acc) => merge_stream_frame(acc, message)?,
+        });
+    }
+
+    let message = merged.ok_or_else(|| invalid_argument("call_stream received no frames"))?;
+
+    let mut call_req = CallRequest::new();
+    call_req.set_request_id(uuid::Uuid::new_v4().as_bytes().to_vec());
+    let mut inner = CallRequestRequest::new();
+    inner.message = Some(message);
+    call_req.set_request(inner);
+    Ok(call_req)
 }
 
-impl RpcRawClient {
-    fn new(channel: ::grpcio::Channel) -> Self {
+/// Mutual-TLS material for a pooled RPC channel, analogous to garage's
+/// `tls_util` certificate bundle: a CA to verify the peer, plus an optional
+/// client cert/key pair for the server to verify this side back.
+#[derive(Clone, Default)]
+pub struct RpcTlsConfig {
+    pub ca_cert: Option<Vec<u8>>,
+    pub cert: Option<Vec<u8>>,
+    pub key: Option<Vec<u8>>,
+}
+
+impl RpcTlsConfig {
+    fn credentials(&self) -> Option<::grpcio::ChannelCredentials> {
+        let ca_cert = self.ca_cert.clone()?;
+        let mut builder = ::grpcio::ChannelCredentialsBuilder::new().root_cert(ca_cert);
+        if let (Some(cert), Some(key)) = (self.cert.clone(), self.key.clone()) {
+            builder = builder.cert(cert, key);
+        }
+        Some(builder.build())
+    }
+}
+
+/// Builder for an `RpcClientPool`: how many channels to keep open to the same
+/// endpoint, HTTP/2 keepalive timing, and optional mutual-TLS credentials.
+/// Mirrors actix's client `Connector`, which pools plain connections the same
+/// way to avoid head-of-line blocking on a single socket.
+#[derive(Clone)]
+pub struct RpcClientPoolBuilder {
+    connections: usize,
+    keepalive_time: Duration,
+    keepalive_timeout: Duration,
+    tls: RpcTlsConfig,
+}
+
+impl Default for RpcClientPoolBuilder {
+    fn default() -> Self {
         Self {
-            client: PbRpcClient::new(channel),
+            connections: 4,
+            keepalive_time: Duration::from_secs(10),
+            keepalive_timeout: Duration::from_secs(3),
+            tls: RpcTlsConfig::default(),
         }
     }
 }
 
+impl RpcClientPoolBuilder {
+    /// Number of independent channels held open to the same endpoint.
+    /// `upload_part` callers that contend heavily on one connection should
+    /// raise this; it defaults to 4.
+    pub fn connections(mut self, connections: usize) -> Self {
+        self.connections = connections.max(1);
+        self
+    }
+
+    pub fn keepalive(mut self, time: Duration, timeout: Duration) -> Self {
+        self.keepalive_time = time;
+        self.keepalive_timeout = timeout;
+        self
+    }
+
+    /// Configures mutual TLS for every channel in the pool. Leaving this
+    /// unset (the default) keeps the connection in plaintext, matching the
+    /// previous unconditional `ChannelBuilder::connect`.
+    pub fn tls(mut self, tls: RpcTlsConfig) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    pub fn build(self, env: Arc<::grpcio::Environment>, addr: &str) -> RpcClientPool {
+        let credentials = self.tls.credentials();
+        let clients = (0..self.connections)
+            .map(|_| {
+                let builder = ::grpcio::ChannelBuilder::new(env.clone())
+                    .keepalive_time(self.keepalive_time)
+                    .keepalive_timeout(self.keepalive_timeout);
+                let channel = match &credentials {
+                    Some(creds) => builder.secure_connect(addr, creds.clone()),
+                    None => builder.connect(addr),
+                };
+                PbRpcClient::new(channel)
+            })
+            .collect();
+
+        RpcClientPool {
+            clients,
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+/// N channels to the same external-storage endpoint, round-robined across by
+/// `RpcRawClient` so concurrent calls (e.g. many in-flight `upload_part`s)
+/// are not serialized behind one HTTP/2 connection's head-of-line blocking.
+#[derive(Clone)]
+pub struct RpcClientPool {
+    clients: Vec<PbRpcClient>,
+    next: Arc<AtomicUsize>,
+}
+
+impl RpcClientPool {
+    /// Wraps a single pre-built channel, for callers that already have one
+    /// and do not need `RpcClientPoolBuilder`'s pooling or TLS options.
+    pub fn single(channel: ::grpcio::Channel) -> Self {
+        Self {
+            clients: vec![PbRpcClient::new(channel)],
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn next_client(&self) -> &PbRpcClient {
+        let idx = self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        &self.clients[idx]
+    }
+}
+
+struct RpcRawClient {
+    pool: RpcClientPool,
+}
+
+impl RpcRawClient {
+    fn new(pool: RpcClientPool) -> Self {
+        Self { pool }
+    }
+}
+
 #[async_trait]
 impl ExternalStorageRawClient for RpcRawClient {
     async fn call(&self, req: &CallRequest) -> RpcErrResult<CallResponse> {
-        Ok(self.client.call_async(req)?.await?)
+        Ok(self.pool.next_client().call_async(req)?.await?)
+    }
+
+    async fn call_stream(&self, mut reqs: BoxStream<'static, CallRequest>) -> RpcErrResult<CallResponse> {
+        let (mut sink, receiver) = self.pool.next_client().call_stream()?;
+        while let Some(req) = reqs.next().await {
+            sink.send((req, ::grpcio::WriteFlags::default())).await?;
+        }
+        sink.close().await?;
+        receiver.await
+    }
+
+    async fn call_response_stream(
+        &self,
+        req: &CallRequest,
+    ) -> RpcErrResult<BoxStream<'static, RpcErrResult<CallResponse>>> {
+        let stream = self.pool.next_client().call_response_stream(req)?;
+        Ok(Box::pin(stream.map_err(::grpcio::Error::from)))
     }
 }
 
+/// Builds a client over a single pre-established channel. Prefer
+/// `new_rpc_client_pool` for deployments that want connection pooling or TLS.
 pub fn new_rpc_client(channel: ::grpcio::Channel) -> ExternalStorageApiClient {
-    ExternalStorageApiClient::new(RpcRawClient::new(channel))
+    ExternalStorageApiClient::new(RpcRawClient::new(RpcClientPool::single(channel)))
+}
+
+pub fn new_rpc_client_pool(pool: RpcClientPool) -> ExternalStorageApiClient {
+    ExternalStorageApiClient::new(RpcRawClient::new(pool))
 }
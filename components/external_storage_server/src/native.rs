@@ -1,13 +1,19 @@
 use futures::channel::oneshot;
+use futures::stream::{BoxStream, StreamExt};
 use std::{collections::HashMap};
+use std::future::Future;
 use std::io;
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use grpcio::RpcStatus;
 use kvproto::extstorepb::{CallRequest, CallResponse};
 use lazy_static::lazy_static;
 use libc::{c_int, c_uchar, c_void};
+use tracing::Instrument;
 
 use crate::util::{pb_marshal, pb_unmarshal};
 use crate::{
@@ -20,9 +26,53 @@ lazy_static! {
     static ref GLOBAL_CLIENT_CONTEXT: Mutex<Option<ClientContext>> = Mutex::new(None);
 }
 
+lazy_static! {
+    // Process-wide fallback runtime, used by `server_external_storage_create_context`
+    // when the host does not supply its own executor via `ServerContext::with_executor`.
+    // Lazily initialized so embedding a single thread pool costs nothing until the
+    // first context actually needs it.
+    static ref SHARED_RUNTIME: Arc<::tokio::runtime::Runtime> = Arc::new(
+        ::tokio::runtime::Runtime::new().expect("failed to build shared external storage runtime")
+    );
+}
+
+/// Returns the process-wide runtime the external storage server falls back to
+/// when it is not handed an executor of its own, so multiple dylib clients in
+/// the same process share one bounded thread pool instead of one-per-call.
+pub fn shared_runtime() -> Arc<::tokio::runtime::Runtime> {
+    SHARED_RUNTIME.clone()
+}
+
+/// The executor a `ServerContext` dispatches service calls onto: either a
+/// runtime it owns outright, or a handle borrowed from a runtime the host
+/// already runs (e.g. TiKV's own thread pool), so the host can configure
+/// worker-thread count centrally instead of TiKV growing one runtime per
+/// embedded service.
+#[derive(Clone)]
+pub enum ServerExecutor {
+    Owned(Arc<::tokio::runtime::Runtime>),
+    Borrowed(::tokio::runtime::Handle),
+}
+
+impl ServerExecutor {
+    fn spawn<F>(&self, fut: F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        match self {
+            ServerExecutor::Owned(rt) => {
+                rt.spawn(fut);
+            }
+            ServerExecutor::Borrowed(handle) => {
+                handle.spawn(fut);
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct ServerContext {
-    runtime: Arc<::tokio::runtime::Runtime>,
+    executor: ServerExecutor,
     service: Arc<dyn ExternalStorageService + Sync + Send>,
 }
 
@@ -30,9 +80,20 @@ impl ServerContext {
     pub fn new(
         runtime: Arc<::tokio::runtime::Runtime>,
         service: impl ExternalStorageService + Sync + Send + 'static,
+    ) -> Self {
+        Self::with_executor(ServerExecutor::Owned(runtime), service)
+    }
+
+    /// Builds a context that dispatches onto an externally owned executor
+    /// instead of spinning up a dedicated runtime, so an embedding process
+    /// can bound its total worker-thread count in one place and avoid
+    /// spawning redundant runtimes per dylib client.
+    pub fn with_executor(
+        executor: ServerExecutor,
+        service: impl ExternalStorageService + Sync + Send + 'static,
     ) -> Self {
         Self {
-            runtime,
+            executor,
             service: Arc::new(service),
         }
     }
@@ -117,6 +178,7 @@ impl ClientContext {
         let (tx, rx) = oneshot::channel::<RpcStatusResult<CallResponse>>();
         let ctx = RequestClientContext::new(request_id.to_owned(), tx);
 
+        tracing::debug!(request_id = ?ctx.id, "recorded outstanding native request");
         requests.insert(ctx.id.clone(), ctx);
 
         rx
@@ -126,11 +188,70 @@ impl ClientContext {
         let mut requests = self.requests.lock().unwrap();
         match requests.remove(request_id) {
             Some(request) => {
+                tracing::debug!(?request_id, "delivering response to outstanding native request");
                 let _ = request.tx.send(result);
             }
-            None => (),
+            None => {
+                tracing::warn!(
+                    ?request_id,
+                    "received a response for a request that is no longer outstanding (timed out, cancelled, or already delivered)"
+                );
+            }
         };
     }
+
+    /// Drops a still-outstanding request without waiting for a response,
+    /// so a timed-out or abandoned call does not hold its entry (and the
+    /// memory behind it) in the map forever. Pairs with `record_request`:
+    /// every recorded request is removed exactly once, whether by
+    /// `response_request`, this method, or the native C side never calling
+    /// back at all.
+    pub fn cancel_request(&self, request_id: &[u8]) {
+        let mut requests = self.requests.lock().unwrap();
+        requests.remove(request_id);
+    }
+}
+
+/// Wraps the oneshot receiver for a single native call so the request is
+/// always evicted from `ClientContext`'s map, even if the returned future is
+/// dropped before it resolves (e.g. the caller was itself cancelled, or lost
+/// a `call_with_deadline` race against the timer).
+struct PendingNativeCall {
+    request_id: Vec<u8>,
+    rx: oneshot::Receiver<RpcStatusResult<CallResponse>>,
+    done: bool,
+}
+
+impl PendingNativeCall {
+    fn new(request_id: Vec<u8>, rx: oneshot::Receiver<RpcStatusResult<CallResponse>>) -> Self {
+        Self {
+            request_id,
+            rx,
+            done: false,
+        }
+    }
+}
+
+impl Future for PendingNativeCall {
+    type Output = Result<RpcStatusResult<CallResponse>, oneshot::Canceled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let poll = Pin::new(&mut self.rx).poll(cx);
+        if poll.is_ready() {
+            self.done = true;
+        }
+        poll
+    }
+}
+
+impl Drop for PendingNativeCall {
+    fn drop(&mut self) {
+        if !self.done {
+            if let Some(ctx) = ClientContext::shared_context() {
+                ctx.cancel_request(&self.request_id);
+            }
+        }
+    }
 }
 
 extern "C" {
@@ -173,34 +294,68 @@ extern "C" fn server_external_storage_async_request(
     let ctx = ctx_result.unwrap();
     let bytes = unsafe { std::slice::from_raw_parts(msg, msg_len as usize) };
     let unmarshal_result = pb_unmarshal::<CallRequest>(bytes);
-    if unmarshal_result.is_err() {
+    if let Err(err) = &unmarshal_result {
+        tracing::warn!(error = %err, "failed to unmarshal CallRequest from the native async_request callback");
         return -1;
     }
 
     let req = unmarshal_result.unwrap();
     let service = ctx.service.clone();
     let request_id = req.get_request_id().to_owned();
-    ctx.runtime.spawn(async move {
-        let result = service.call(req).await;
-        let resp = match result {
-            Ok(res) => res,
-            Err(err) => {
-                let mut res = CallResponse::new();
-                res.set_request_id(request_id);
-                put_status_error_to_call_response(&mut res, &err);
-
-                res
-            }
-        };
-
-        let resp_bytes = pb_marshal(&resp).unwrap();
-        cb_func(
-            resp_bytes.as_ptr(),
-            resp_bytes.len() as c_int,
-            std::ptr::null(),
-            0,
-        );
-    });
+    let trace_id = if req.has_header() {
+        req.get_header().get_trace_id().to_owned()
+    } else {
+        String::new()
+    };
+    let deadline = if req.has_header() && req.get_header().get_deadline_ms() > 0 {
+        Some(Duration::from_millis(req.get_header().get_deadline_ms()))
+    } else {
+        None
+    };
+    // Re-enters the trace context the client opened, carried across the
+    // `extern "C"` boundary via the header rather than any Rust call stack,
+    // so `service.call`'s logs and this callback's completion are stitched
+    // back to the same trace the client started.
+    let span = tracing::info_span!(
+        "external_storage_dispatch",
+        request_id = ?request_id,
+        trace_id = %trace_id,
+    );
+    ctx.executor.spawn(
+        async move {
+            tracing::debug!("dispatching native call");
+            let result = match deadline {
+                Some(deadline) => match ::tokio::time::timeout(deadline, service.call(req)).await {
+                    Ok(result) => result,
+                    Err(_) => Err(::grpcio::RpcStatus::new(
+                        ::grpcio::RpcStatusCode::DEADLINE_EXCEEDED,
+                        Some(format!("call exceeded its {:?} deadline", deadline)),
+                    )),
+                },
+                None => service.call(req).await,
+            };
+            let resp = match result {
+                Ok(res) => res,
+                Err(err) => {
+                    let mut res = CallResponse::new();
+                    res.set_request_id(request_id);
+                    put_status_error_to_call_response(&mut res, &err);
+
+                    res
+                }
+            };
+
+            let resp_bytes = pb_marshal(&resp).unwrap();
+            tracing::debug!("native call complete, invoking callback");
+            cb_func(
+                resp_bytes.as_ptr(),
+                resp_bytes.len() as c_int,
+                std::ptr::null(),
+                0,
+            );
+        }
+        .instrument(span),
+    );
 
     0
 }
@@ -239,10 +394,7 @@ impl NativeRawClient {
         Self {}
     }
 
-    fn call_native(
-        &self,
-        req: &CallRequest,
-    ) -> RpcErrResult<oneshot::Receiver<RpcStatusResult<CallResponse>>> {
+    fn call_native(&self, req: &CallRequest) -> RpcErrResult<PendingNativeCall> {
         let ctx = ClientContext::shared_context();
         if ctx.is_none() {
             return Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
@@ -269,26 +421,114 @@ impl NativeRawClient {
         }
 
         let ctx = ctx.unwrap();
-        Ok(ctx.record_request(req.get_request_id()))
+        Ok(PendingNativeCall::new(
+            req.get_request_id().to_owned(),
+            ctx.record_request(req.get_request_id()),
+        ))
+    }
+
+    /// Ships one more frame of an in-flight streaming request across the FFI
+    /// boundary, under the `request_id` a prior `call_native` already
+    /// recorded. No new entry is recorded in `ClientContext`: the dylib side
+    /// is expected to demultiplex frames that share a `request_id` onto the
+    /// same in-flight stream it started on the first frame, and to invoke the
+    /// callback exactly once, for the whole stream, once it has seen the
+    /// last one.
+    fn send_native_stream_frame(&self, req: &CallRequest) -> RpcErrResult<()> {
+        let bytes = pb_marshal(req)?;
+        let code = server_external_storage_async_request(
+            bytes.as_ptr(),
+            bytes.len() as c_int,
+            std::ptr::null(),
+            0,
+            client_external_storage_callback,
+        );
+
+        if code != 0 {
+            return Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                ::grpcio::RpcStatusCode::INTERNAL,
+                Some(format!("async call refused, code: {}", code)),
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn native_result_to_call_result(
+    wait_result: Result<RpcStatusResult<CallResponse>, oneshot::Canceled>,
+) -> RpcErrResult<CallResponse> {
+    match wait_result {
+        Ok(Ok(resp)) => Ok(resp),
+        Ok(Err(err)) => Err(::grpcio::Error::RpcFailure(err)),
+        Err(canceled) => Err(::grpcio::Error::RpcFailure(RpcStatus::new(
+            ::grpcio::RpcStatusCode::ABORTED,
+            Some(format!("{}", canceled)),
+        ))),
     }
 }
 
 #[async_trait]
 impl ExternalStorageRawClient for NativeRawClient {
     async fn call(&self, req: &CallRequest) -> RpcErrResult<CallResponse> {
-        let rx = self.call_native(req)?;
-        let wait_result = rx.await;
-        if wait_result.is_err() {
-            return Err(::grpcio::Error::RpcFailure(RpcStatus::new(
-                ::grpcio::RpcStatusCode::ABORTED,
-                Some(format!("{}", wait_result.unwrap_err())),
-            )));
+        let pending = self.call_native(req)?;
+        native_result_to_call_result(pending.await)
+    }
+
+    async fn call_with_deadline(
+        &self,
+        req: &CallRequest,
+        deadline: Option<Duration>,
+    ) -> RpcErrResult<CallResponse> {
+        let pending = self.call_native(req)?;
+        let timeout = match deadline {
+            Some(timeout) => timeout,
+            None => return native_result_to_call_result(pending.await),
+        };
+
+        ::tokio::select! {
+            result = pending => native_result_to_call_result(result),
+            _ = ::tokio::time::sleep(timeout) => {
+                // Dropping `pending` here runs `PendingNativeCall::drop`, which
+                // evicts the request from `ClientContext` so the native side's
+                // eventual (or never-arriving) callback finds nothing to
+                // deliver to.
+                Err(::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                    ::grpcio::RpcStatusCode::DEADLINE_EXCEEDED,
+                    Some(format!("native call timed out after {:?}", timeout)),
+                )))
+            }
         }
+    }
 
-        match wait_result.unwrap() {
-            Ok(resp) => Ok(resp),
-            Err(err) => Err(::grpcio::Error::RpcFailure(err)),
+    async fn call_stream(&self, mut reqs: BoxStream<'static, CallRequest>) -> RpcErrResult<CallResponse> {
+        // All frames share the first frame's `request_id`: only that first
+        // send records an entry in `ClientContext`, every following frame
+        // rides the same in-flight stream on the dylib side, and the single
+        // resulting callback resolves the one `PendingNativeCall` recorded up
+        // front. This lets a multi-gigabyte upload cross the FFI boundary
+        // without a full request/response round trip per chunk.
+        let first = reqs.next().await.ok_or_else(|| {
+            ::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+                ::grpcio::RpcStatusCode::INVALID_ARGUMENT,
+                Some("call_stream received no frames".to_owned()),
+            ))
+        })?;
+        let pending = self.call_native(&first)?;
+
+        while let Some(req) = reqs.next().await {
+            self.send_native_stream_frame(&req)?;
         }
+
+        native_result_to_call_result(pending.await)
+    }
+
+    async fn call_response_stream(
+        &self,
+        req: &CallRequest,
+    ) -> RpcErrResult<BoxStream<'static, RpcErrResult<CallResponse>>> {
+        let resp = self.call(req).await?;
+        Ok(Box::pin(futures::stream::once(async { Ok(resp) })))
     }
 }
 
@@ -0,0 +1,199 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use kvproto::extstorepb::{CallRequest, CallResponse};
+use rand::Rng;
+
+use crate::def::*;
+
+/// Exponential backoff and retry budget for `RetryRawClient`.
+#[derive(Clone, Debug)]
+pub struct RetryConfig {
+    /// Maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub backoff_multiplier: f64,
+    /// Caps the wall-clock time spent retrying a single call, regardless of
+    /// how many attempts remain.
+    pub total_deadline: Option<Duration>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            backoff_multiplier: 2.0,
+            total_deadline: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+impl RetryConfig {
+    fn backoff_for_attempt(&self, attempt: u32) -> Duration {
+        let exp = self
+            .backoff_multiplier
+            .powi(attempt.saturating_sub(1) as i32);
+        let backoff = self.initial_backoff.mul_f64(exp).min(self.max_backoff);
+        let jitter = rand::thread_rng().gen_range(0.5..1.0);
+        backoff.mul_f64(jitter)
+    }
+}
+
+/// Returned once a call has exhausted its retry budget, so callers can tell a
+/// truly unreachable backend apart from a single failed attempt.
+#[derive(Debug)]
+pub struct RetriesExhausted {
+    pub attempts: u32,
+    pub last_error: ::grpcio::Error,
+}
+
+impl fmt::Display for RetriesExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "retries exhausted after {} attempt(s), last error: {}",
+            self.attempts, self.last_error
+        )
+    }
+}
+
+impl std::error::Error for RetriesExhausted {}
+
+impl From<RetriesExhausted> for ::grpcio::Error {
+    fn from(err: RetriesExhausted) -> Self {
+        ::grpcio::Error::RpcFailure(::grpcio::RpcStatus::new(
+            ::grpcio::RpcStatusCode::UNAVAILABLE,
+            Some(err.to_string()),
+        ))
+    }
+}
+
+/// Classifies a raw-client error as transient (worth retrying) or permanent.
+///
+/// `RpcFailure` statuses map a fixed set of codes that upstream providers
+/// typically use for throttling and transient unavailability (`UNAVAILABLE`,
+/// `DEADLINE_EXCEEDED`, `RESOURCE_EXHAUSTED`, which is where S3-style 429/503
+/// responses land once passed through `check_status_of_call_response`).
+/// Anything else, including codec/connection errors that are not tied to a
+/// status code, is treated as permanent.
+pub fn is_transient(err: &::grpcio::Error) -> bool {
+    match err {
+        ::grpcio::Error::RpcFailure(status) => matches!(
+            status.code(),
+            ::grpcio::RpcStatusCode::UNAVAILABLE
+                | ::grpcio::RpcStatusCode::DEADLINE_EXCEEDED
+                | ::grpcio::RpcStatusCode::RESOURCE_EXHAUSTED
+        ),
+        _ => false,
+    }
+}
+
+/// Wraps a raw client with retry-with-backoff for transient failures. Stacks
+/// over `DirectRawClient`, the gRPC client, or the dylib client uniformly,
+/// since they all speak the same `ExternalStorageRawClient` trait.
+pub struct RetryRawClient<T> {
+    inner: T,
+    config: RetryConfig,
+}
+
+impl<T> RetryRawClient<T> {
+    pub fn new(inner: T, config: RetryConfig) -> Self {
+        Self { inner, config }
+    }
+}
+
+pub fn new_retry_client(
+    inner: impl ExternalStorageRawClient + Send + Sync + 'static,
+    config: RetryConfig,
+) -> ExternalStorageApiClient {
+    ExternalStorageApiClient::new(RetryRawClient::new(inner, config))
+}
+
+#[async_trait]
+impl<T: ExternalStorageRawClient + Send + Sync> ExternalStorageRawClient for RetryRawClient<T> {
+    async fn call(&self, req: &CallRequest) -> RpcErrResult<CallResponse> {
+        let deadline = self
+            .config
+            .total_deadline
+            .map(|deadline| Instant::now() + deadline);
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.inner.call(req).await {
+                Ok(resp) => return Ok(resp),
+                Err(err) => {
+                    let out_of_attempts = attempt >= self.config.max_attempts;
+                    let out_of_time = deadline.is_some_and(|d| Instant::now() >= d);
+                    if out_of_attempts || out_of_time || !is_transient(&err) {
+                        return Err(if is_transient(&err) {
+                            RetriesExhausted {
+                                attempts: attempt,
+                                last_error: err,
+                            }
+                            .into()
+                        } else {
+                            err
+                        });
+                    }
+                    ::tokio::time::sleep(self.config.backoff_for_attempt(attempt)).await;
+                }
+            }
+        }
+    }
+
+    async fn call_with_deadline(
+        &self,
+        req: &CallRequest,
+        deadline: Option<Duration>,
+    ) -> RpcErrResult<CallResponse> {
+        let retry_deadline = self
+            .config
+            .total_deadline
+            .map(|d| Instant::now() + d);
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.inner.call_with_deadline(req, deadline).await {
+                Ok(resp) => return Ok(resp),
+                Err(err) => {
+                    let out_of_attempts = attempt >= self.config.max_attempts;
+                    let out_of_time = retry_deadline.is_some_and(|d| Instant::now() >= d);
+                    if out_of_attempts || out_of_time || !is_transient(&err) {
+                        return Err(if is_transient(&err) {
+                            RetriesExhausted {
+                                attempts: attempt,
+                                last_error: err,
+                            }
+                            .into()
+                        } else {
+                            err
+                        });
+                    }
+                    ::tokio::time::sleep(self.config.backoff_for_attempt(attempt)).await;
+                }
+            }
+        }
+    }
+
+    // Streaming calls are not idempotent once the caller's stream has started
+    // being drained, so they are forwarded as-is instead of retried.
+    async fn call_stream(&self, reqs: BoxStream<'static, CallRequest>) -> RpcErrResult<CallResponse> {
+        self.inner.call_stream(reqs).await
+    }
+
+    async fn call_response_stream(
+        &self,
+        req: &CallRequest,
+    ) -> RpcErrResult<BoxStream<'static, RpcErrResult<CallResponse>>> {
+        self.inner.call_response_stream(req).await
+    }
+}
@@ -1,15 +1,60 @@
-use crate::{ExternalStorageService, RpcStatusResult};
+use crate::credentials::{
+    CachingCredentialProvider, CredentialProvider, EnvCredentialSource, RusotoCredentialsAdapter,
+    WebIdentityCredentialSource, DEFAULT_REFRESH_WINDOW,
+};
+use crate::def::{ExternalStorageService, RpcStatusResult};
 use async_trait::async_trait;
 use external_storage::{AsyncExternalStorage, AsyncUploader};
+use futures::io::AsyncReadExt;
 use grpcio::{RpcStatus, RpcStatusCode};
 use kvproto::extstorepb::*;
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
+use tracing::warn;
 
 use uuid::Uuid;
 
+/// Builds the credential provider a `create_store` request asked for, or
+/// `None` to fall back to the store config's own static keys / rusoto's
+/// default chain (today's behavior).
+fn build_credential_provider(
+    req: &CreateStoreRequest,
+) -> RpcStatusResult<Option<Arc<dyn CredentialProvider>>> {
+    match req.get_credential_mode() {
+        "" | "static" => Ok(None),
+        "env" => Ok(Some(Arc::new(CachingCredentialProvider::new(
+            EnvCredentialSource,
+            DEFAULT_REFRESH_WINDOW,
+        )))),
+        "web_identity" => {
+            let region = rusoto_util::get_region(
+                req.get_s3().region.as_ref(),
+                req.get_s3().endpoint.as_ref(),
+            )
+            .map_err(|err| {
+                RpcStatus::new(RpcStatusCode::INVALID_ARGUMENT, Some(format!("{}", err)))
+            })?;
+            let source = WebIdentityCredentialSource::new(
+                req.get_web_identity_token_file(),
+                req.get_web_identity_role_arn(),
+                req.get_web_identity_session_name(),
+                region,
+            );
+            Ok(Some(Arc::new(CachingCredentialProvider::new(
+                source,
+                DEFAULT_REFRESH_WINDOW,
+            ))))
+        }
+        other => Err(RpcStatus::new(
+            RpcStatusCode::INVALID_ARGUMENT,
+            Some(format!("unknown credential_mode {}", other)),
+        )),
+    }
+}
+
 type Storages = Arc<Mutex<HashMap<String, StoreWrapper>>>;
 type Uploaders = Arc<Mutex<HashMap<String, UploaderWrapper>>>;
 
@@ -25,6 +70,67 @@ struct UploaderWrapper {
     uploader: Arc<dyn AsyncUploader>,
     uploader_id: String,
     store_id: String,
+    /// Shared so every clone pulled out of the `uploaders` map observes the
+    /// same last-activity stamp; the reaper reads it, `upload_part` bumps it.
+    last_active: Arc<Mutex<Instant>>,
+}
+
+/// Bounds how long an idle multipart upload is allowed to hold server
+/// memory. Mirrors `RetryConfig`'s shape: a plain config struct with a
+/// `Default` the service falls back to when the caller doesn't care.
+#[derive(Clone, Debug)]
+pub struct UploaderReaperConfig {
+    /// An uploader with no `create_uploader`/`upload_part` activity for this
+    /// long is aborted and evicted.
+    pub ttl: Duration,
+    /// How often the reaper sweeps `uploaders` for expired entries.
+    pub interval: Duration,
+}
+
+impl Default for UploaderReaperConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(3600),
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Periodically evicts uploaders idle beyond `config.ttl`, aborting each on
+/// the way out so the backend doesn't keep a dangling multipart session of
+/// its own. Runs for the lifetime of the service's tokio runtime.
+async fn run_uploader_reaper(uploaders: Uploaders, config: UploaderReaperConfig) {
+    loop {
+        ::tokio::time::sleep(config.interval).await;
+
+        let expired: Vec<UploaderWrapper> = {
+            let mut uploaders = uploaders.lock().unwrap();
+            let now = Instant::now();
+            let expired_ids: Vec<String> = uploaders
+                .iter()
+                .filter(|(_, wrapper)| {
+                    now.duration_since(*wrapper.last_active.lock().unwrap()) >= config.ttl
+                })
+                .map(|(id, _)| id.clone())
+                .collect();
+
+            expired_ids
+                .into_iter()
+                .filter_map(|id| uploaders.remove(&id))
+                .collect()
+        };
+
+        for wrapper in expired {
+            if let Err(err) = wrapper.uploader.clone().abort_async().await {
+                warn!(
+                    uploader_id = %wrapper.uploader_id,
+                    store_id = %wrapper.store_id,
+                    error = %err,
+                    "failed to abort idle uploader during reclamation"
+                );
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -36,11 +142,18 @@ pub struct DefaultExternalStorageService {
 
 impl DefaultExternalStorageService {
     pub fn new() -> Self {
+        Self::with_reaper_config(UploaderReaperConfig::default())
+    }
+
+    pub fn with_reaper_config(config: UploaderReaperConfig) -> Self {
         let threaded_rt = ::tokio::runtime::Runtime::new().unwrap();
+        let uploaders: Uploaders = Arc::new(Mutex::new(HashMap::new()));
+
+        threaded_rt.spawn(run_uploader_reaper(uploaders.clone(), config));
 
         Self {
             storages: Arc::new(Mutex::new(HashMap::new())),
-            uploaders: Arc::new(Mutex::new(HashMap::new())),
+            uploaders,
             rt: Arc::new(threaded_rt),
         }
     }
@@ -133,6 +246,72 @@ impl ExternalStorageService for DefaultExternalStorageService {
         Ok(resp)
     }
 
+    async fn create_store(&self, req: CreateStoreRequest) -> RpcStatusResult<CreateStoreResponse> {
+        let provider = req.get_provider().to_owned();
+        let credential_provider = build_credential_provider(&req)?;
+        let store: Arc<dyn AsyncExternalStorage> = match provider.as_str() {
+            "s3" => {
+                let result = match credential_provider {
+                    Some(provider) => external_storage::S3Storage::with_credentials_provider(
+                        req.get_s3(),
+                        RusotoCredentialsAdapter::new(provider),
+                    ),
+                    None => external_storage::S3Storage::new(req.get_s3()),
+                };
+                Arc::new(result.map_err(|err| {
+                    RpcStatus::new(
+                        RpcStatusCode::INVALID_ARGUMENT,
+                        Some(format!("failed to build s3 store, {}", err)),
+                    )
+                })?)
+            }
+            // Azure Blob and GCS have no `AsyncExternalStorage` implementation
+            // in this build, so they are rejected up front as an invalid
+            // provider rather than accepted and only failing once a store is
+            // actually needed.
+            // TODO: multi-provider `create_store`/`delete_store` support is
+            // still only one of three backends (s3). Azure Blob and GCS
+            // remain an open follow-up, not delivered by this rejection.
+            _ => {
+                return Err(RpcStatus::new(
+                    RpcStatusCode::INVALID_ARGUMENT,
+                    Some(format!("unknown provider {}", provider)),
+                ))
+            }
+        };
+
+        let store_id = Uuid::new_v4().to_string();
+        let mut storages = self.storages.lock().unwrap();
+        storages.insert(
+            store_id.clone(),
+            StoreWrapper {
+                store,
+                store_id: store_id.clone(),
+                provider: provider.clone(),
+            },
+        );
+
+        let mut store_pb = Store::new();
+        store_pb.set_id(store_id);
+        store_pb.set_provider(provider);
+
+        let mut resp = CreateStoreResponse::new();
+        resp.set_store(store_pb);
+        Ok(resp)
+    }
+
+    async fn delete_store(&self, req: DeleteStoreRequest) -> RpcStatusResult<DeleteStoreResponse> {
+        let mut storages = self.storages.lock().unwrap();
+        storages.remove(req.get_store_id()).ok_or_else(|| {
+            RpcStatus::new(
+                RpcStatusCode::NOT_FOUND,
+                Some(format!("cannot find store with id: {}", req.get_store_id())),
+            )
+        })?;
+
+        Ok(DeleteStoreResponse::new())
+    }
+
     async fn write_file(&self, req: WriteFileRequest) -> RpcStatusResult<WriteFileResponse> {
         let buf = req.get_data();
         let result = self
@@ -151,6 +330,124 @@ impl ExternalStorageService for DefaultExternalStorageService {
         }
     }
 
+    async fn read_file(&self, req: ReadFileRequest) -> RpcStatusResult<ReadFileResponse> {
+        let store = self.find_store(req.get_store_id())?.store.clone();
+
+        let mut reader = store.read_async(req.get_filepath()).await.map_err(|err| {
+            RpcStatus::new(
+                RpcStatusCode::NOT_FOUND,
+                Some(format!(
+                    "cannot find file {} in store {}: {}",
+                    req.get_filepath(),
+                    req.get_store_id(),
+                    err
+                )),
+            )
+        })?;
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await.map_err(|err| {
+            RpcStatus::new(
+                RpcStatusCode::INTERNAL,
+                Some(format!("failed to read file, {}", err)),
+            )
+        })?;
+
+        // `offset == len` (reading zero bytes right at the end) is
+        // satisfiable; only a start strictly past the end of the object
+        // is not.
+        let offset = req.get_offset() as usize;
+        if offset > data.len() {
+            return Err(RpcStatus::new(
+                RpcStatusCode::INVALID_ARGUMENT,
+                Some(format!(
+                    "range start {} is past the end of a {}-byte object",
+                    offset,
+                    data.len()
+                )),
+            ));
+        }
+
+        // `length == 0` means an open-ended range (`offset-`): everything
+        // from `offset` to the end of the object.
+        let end = if req.get_length() > 0 {
+            data.len().min(offset + req.get_length() as usize)
+        } else {
+            data.len()
+        };
+
+        let mut resp = ReadFileResponse::new();
+        resp.set_data(data[offset..end].to_vec());
+        Ok(resp)
+    }
+
+    async fn list_files(&self, req: ListFilesRequest) -> RpcStatusResult<ListFilesResponse> {
+        let store = self.find_store(req.get_store_id())?.store.clone();
+        let max_keys = if req.get_max_keys() > 0 {
+            req.get_max_keys() as usize
+        } else {
+            1000
+        };
+
+        // Drives the provider's own pagination (S3/GCS/Azure all thread a
+        // next-page marker through successive list calls) until either the
+        // page fills `max_keys` or the provider reports no further marker,
+        // then hands back whatever marker is left over as our own opaque
+        // `continuation_token` for the caller's next request.
+        let mut keys = Vec::new();
+        let mut marker = req.get_continuation_token().to_owned();
+        loop {
+            let (page, next_marker) = store
+                .list_async(req.get_prefix(), &marker)
+                .await
+                .map_err(|err| {
+                    RpcStatus::new(
+                        RpcStatusCode::INTERNAL,
+                        Some(format!("failed to list files, {}", err)),
+                    )
+                })?;
+
+            keys.extend(page);
+            marker = next_marker;
+
+            if keys.len() >= max_keys || marker.is_empty() {
+                break;
+            }
+        }
+        keys.truncate(max_keys);
+
+        let mut resp = ListFilesResponse::new();
+        resp.set_keys(protobuf::RepeatedField::from_vec(keys));
+        resp.set_continuation_token(marker);
+        Ok(resp)
+    }
+
+    async fn head_object(&self, req: HeadObjectRequest) -> RpcStatusResult<HeadObjectResponse> {
+        let store = self.find_store(req.get_store_id())?.store.clone();
+
+        let (content_length, last_modified, etag, content_type) = store
+            .stat_async(req.get_filepath())
+            .await
+            .map_err(|err| {
+                RpcStatus::new(
+                    RpcStatusCode::NOT_FOUND,
+                    Some(format!(
+                        "cannot find file {} in store {}: {}",
+                        req.get_filepath(),
+                        req.get_store_id(),
+                        err
+                    )),
+                )
+            })?;
+
+        let mut resp = HeadObjectResponse::new();
+        resp.set_content_length(content_length);
+        resp.set_last_modified(last_modified);
+        resp.set_etag(etag);
+        resp.set_content_type(content_type);
+        Ok(resp)
+    }
+
     async fn create_uploader(&self, req: CreateUploaderRequest) -> RpcStatusResult<CreateUploaderResponse> {
         let store = self.find_store(req.get_store_id())?.store.clone();
 
@@ -162,6 +459,7 @@ impl ExternalStorageService for DefaultExternalStorageService {
                 store_id: req.get_store_id().to_owned(),
                 uploader_id: uploader_id.clone(),
                 uploader: store.create_uploader(req.get_filepath()),
+                last_active: Arc::new(Mutex::new(Instant::now())),
             },
         );
 
@@ -176,8 +474,10 @@ impl ExternalStorageService for DefaultExternalStorageService {
     }
 
     async fn upload_part(&self, req: UploadPartRequest) -> RpcStatusResult<UploadPartResponse> {
-        let result = self
-            .find_store_uploader(req.get_store_id(), req.get_uploader_id())?
+        let wrapper = self.find_store_uploader(req.get_store_id(), req.get_uploader_id())?;
+        *wrapper.last_active.lock().unwrap() = Instant::now();
+
+        let result = wrapper
             .uploader
             .clone()
             .upload_part_async(req.get_part_number(), req.get_data())
@@ -201,7 +501,10 @@ impl ExternalStorageService for DefaultExternalStorageService {
             .await;
 
         match result {
-            Ok(_) => Ok(CompleteUploadResponse::new()),
+            Ok(_) => {
+                self.uploaders.lock().unwrap().remove(req.get_uploader_id());
+                Ok(CompleteUploadResponse::new())
+            }
             Err(err) => Err(RpcStatus::new(
                 RpcStatusCode::INTERNAL,
                 Some(format!("failed to complete upload, {}", err)),
@@ -218,7 +521,10 @@ impl ExternalStorageService for DefaultExternalStorageService {
             .await;
 
         match result {
-            Ok(_) => Ok(AbortUploadResponse::new()),
+            Ok(_) => {
+                self.uploaders.lock().unwrap().remove(req.get_uploader_id());
+                Ok(AbortUploadResponse::new())
+            }
             Err(err) => Err(RpcStatus::new(
                 RpcStatusCode::INTERNAL,
                 Some(format!("failed to abort upload, {}", err)),